@@ -0,0 +1,169 @@
+//! Persistent traversal checkpoints.
+//!
+//! A [`CheckpointCache`] remembers the last validated hop of a gibbername's Catena chain so
+//! `lookup` and `lookup_whole_history` can resume traversal from there instead of re-walking from
+//! the name's registration on every call.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use melstructs::{BlockHeight, CoinData, Denom, TxHash};
+use serde::{Deserialize, Serialize};
+
+/// A resume point for a gibbername's forward traversal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The height at which `last_txhash` actually confirmed.
+    pub resume_height: BlockHeight,
+    /// The hash of the last validated hop.
+    pub last_txhash: TxHash,
+    /// The gibbercoin's permanent denom, `Denom::Custom(start_txhash)`.
+    pub denom: Denom,
+    /// Every hop's coin data validated so far, from the registration output onward.
+    pub history: Vec<CoinData>,
+}
+
+/// Caches the last validated [`Checkpoint`] for a gibbername.
+///
+/// Implementations must treat a missing entry the same as a cache miss -- callers fall back to
+/// traversing from the name's registration.
+pub trait CheckpointCache: Send + Sync {
+    fn get(&self, gibbername: &str) -> anyhow::Result<Option<Checkpoint>>;
+    fn put(&self, gibbername: &str, checkpoint: Checkpoint) -> anyhow::Result<()>;
+    fn invalidate(&self, gibbername: &str) -> anyhow::Result<()>;
+}
+
+/// An in-memory checkpoint cache, good for a single long-lived process.
+#[derive(Default)]
+pub struct MemoryCheckpointCache(Mutex<HashMap<String, Checkpoint>>);
+
+impl MemoryCheckpointCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CheckpointCache for MemoryCheckpointCache {
+    fn get(&self, gibbername: &str) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(self.0.lock().unwrap().get(gibbername).cloned())
+    }
+
+    fn put(&self, gibbername: &str, checkpoint: Checkpoint) -> anyhow::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(gibbername.to_string(), checkpoint);
+        Ok(())
+    }
+
+    fn invalidate(&self, gibbername: &str) -> anyhow::Result<()> {
+        self.0.lock().unwrap().remove(gibbername);
+        Ok(())
+    }
+}
+
+/// An on-disk checkpoint cache backed by a single JSON file, for persistence across restarts.
+pub struct FileCheckpointCache {
+    path: PathBuf,
+}
+
+impl FileCheckpointCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn load(&self) -> anyhow::Result<HashMap<String, Checkpoint>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, map: &HashMap<String, Checkpoint>) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec_pretty(map)?;
+        fs::write(&self.path, bytes)?;
+        Ok(())
+    }
+}
+
+impl CheckpointCache for FileCheckpointCache {
+    fn get(&self, gibbername: &str) -> anyhow::Result<Option<Checkpoint>> {
+        Ok(self.load()?.get(gibbername).cloned())
+    }
+
+    fn put(&self, gibbername: &str, checkpoint: Checkpoint) -> anyhow::Result<()> {
+        let mut map = self.load()?;
+        map.insert(gibbername.to_string(), checkpoint);
+        self.save(&map)
+    }
+
+    fn invalidate(&self, gibbername: &str) -> anyhow::Result<()> {
+        let mut map = self.load()?;
+        map.remove(gibbername);
+        self.save(&map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use melstructs::{Address, CoinValue};
+    use std::str::FromStr;
+
+    fn sample_checkpoint() -> Checkpoint {
+        let denom = Denom::NewCustom;
+        Checkpoint {
+            resume_height: BlockHeight(216),
+            last_txhash: TxHash::default(),
+            denom,
+            history: vec![CoinData {
+                covhash: Address::from_str(
+                    "t2k917e3f3r6wk5474sg3exmfpkh04a42w1chmek68fv5pnygywvsg",
+                )
+                .unwrap(),
+                value: CoinValue(1),
+                denom,
+                additional_data: b"henlo world lmao".to_vec().into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn memory_cache_roundtrips() {
+        let cache = MemoryCheckpointCache::new();
+        assert!(cache.get("hehe-lol").unwrap().is_none());
+
+        cache.put("hehe-lol", sample_checkpoint()).unwrap();
+        let got = cache.get("hehe-lol").unwrap().unwrap();
+        assert_eq!(got.resume_height, BlockHeight(216));
+
+        cache.invalidate("hehe-lol").unwrap();
+        assert!(cache.get("hehe-lol").unwrap().is_none());
+    }
+
+    #[test]
+    fn file_cache_roundtrips_across_instances() {
+        let path = std::env::temp_dir().join(format!(
+            "gibbername-checkpoint-test-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let cache = FileCheckpointCache::new(path.clone());
+        assert!(cache.get("hehe-lol").unwrap().is_none());
+        cache.put("hehe-lol", sample_checkpoint()).unwrap();
+
+        // A fresh instance pointed at the same path sees what was persisted.
+        let reopened = FileCheckpointCache::new(path.clone());
+        let got = reopened.get("hehe-lol").unwrap().unwrap();
+        assert_eq!(got.resume_height, BlockHeight(216));
+
+        reopened.invalidate("hehe-lol").unwrap();
+        assert!(cache.get("hehe-lol").unwrap().is_none());
+
+        let _ = fs::remove_file(&path);
+    }
+}
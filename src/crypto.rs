@@ -0,0 +1,130 @@
+//! Optional end-to-end encryption of bindings, via a viewing-key model.
+//!
+//! An [`Envelope`] is an ECDH ephemeral-key + AEAD construction stored directly in a coin's
+//! `additional_data`, behind a marker byte that distinguishes it from a plaintext `gibbername-v1`
+//! string or `gibbername-v2` record. The chain itself -- denom, value, transfer history -- stays
+//! publicly auditable; only the bound content is sealed.
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use rand::rngs::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// The marker byte identifying an encrypted binding envelope in `additional_data`.
+pub const ENVELOPE_TAG: u8 = 0xfe;
+
+/// A fixed nonce is safe here because every envelope derives its AEAD key from a fresh ephemeral
+/// keypair, so the (key, nonce) pair is never reused.
+const NONCE: &[u8; 12] = b"gibbername!!";
+
+/// An encrypted binding: an ephemeral public key plus the authenticated ciphertext it unlocks.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub ephemeral_pubkey: [u8; 32],
+    pub ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    /// Whether `additional_data` looks like an encrypted envelope, as opposed to a plaintext
+    /// v1/v2 record.
+    pub fn is_envelope(additional_data: &[u8]) -> bool {
+        additional_data.first() == Some(&ENVELOPE_TAG)
+    }
+
+    /// Serializes this envelope for storage in a coin's `additional_data`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 32 + self.ciphertext.len());
+        out.push(ENVELOPE_TAG);
+        out.extend_from_slice(&self.ephemeral_pubkey);
+        out.extend_from_slice(&self.ciphertext);
+        out
+    }
+
+    /// Parses an envelope out of a coin's `additional_data`.
+    pub fn decode(additional_data: &[u8]) -> anyhow::Result<Self> {
+        if !Self::is_envelope(additional_data) {
+            anyhow::bail!("not an encrypted binding envelope");
+        }
+        if additional_data.len() < 1 + 32 {
+            anyhow::bail!("truncated encrypted binding envelope");
+        }
+        Ok(Self {
+            ephemeral_pubkey: additional_data[1..33].try_into().unwrap(),
+            ciphertext: additional_data[33..].to_vec(),
+        })
+    }
+}
+
+fn shared_key(our_secret: &StaticSecret, their_public: &PublicKey) -> [u8; 32] {
+    *blake3::hash(our_secret.diffie_hellman(their_public).as_bytes()).as_bytes()
+}
+
+/// Encrypts `plaintext` for `recipient_pubkey`, generating a fresh ephemeral keypair for this
+/// envelope alone.
+pub fn encrypt(recipient_pubkey: &PublicKey, plaintext: &[u8]) -> Envelope {
+    let ephemeral_secret = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_pubkey = PublicKey::from(&ephemeral_secret);
+    let key = shared_key(&ephemeral_secret, recipient_pubkey);
+    let cipher = ChaCha20Poly1305::new(key.as_slice().into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(NONCE), plaintext)
+        .expect("encryption under a freshly-derived key cannot fail");
+    Envelope {
+        ephemeral_pubkey: ephemeral_pubkey.to_bytes(),
+        ciphertext,
+    }
+}
+
+/// Decrypts an envelope using the recipient's viewing secret key.
+pub fn decrypt(viewing_secret: &StaticSecret, envelope: &Envelope) -> anyhow::Result<Vec<u8>> {
+    let ephemeral_pubkey = PublicKey::from(envelope.ephemeral_pubkey);
+    let key = shared_key(viewing_secret, &ephemeral_pubkey);
+    let cipher = ChaCha20Poly1305::new(key.as_slice().into());
+    cipher
+        .decrypt(Nonce::from_slice(NONCE), envelope.ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("failed to decrypt binding: wrong viewing key or corrupt data"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_roundtrips() {
+        let recipient_secret = StaticSecret::random_from_rng(OsRng);
+        let recipient_pubkey = PublicKey::from(&recipient_secret);
+
+        let envelope = encrypt(&recipient_pubkey, b"henlo world lmao");
+        let plaintext = decrypt(&recipient_secret, &envelope).unwrap();
+
+        assert_eq!(plaintext, b"henlo world lmao");
+    }
+
+    #[test]
+    fn wrong_viewing_key_fails_to_decrypt() {
+        let recipient_pubkey = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let wrong_secret = StaticSecret::random_from_rng(OsRng);
+
+        let envelope = encrypt(&recipient_pubkey, b"henlo world lmao");
+
+        assert!(decrypt(&wrong_secret, &envelope).is_err());
+    }
+
+    #[test]
+    fn envelope_encode_decode_roundtrips() {
+        let recipient_pubkey = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let envelope = encrypt(&recipient_pubkey, b"henlo world lmao");
+
+        let encoded = envelope.encode();
+        assert!(Envelope::is_envelope(&encoded));
+
+        let decoded = Envelope::decode(&encoded).unwrap();
+        assert_eq!(decoded.ephemeral_pubkey, envelope.ephemeral_pubkey);
+        assert_eq!(decoded.ciphertext, envelope.ciphertext);
+    }
+
+    #[test]
+    fn plaintext_record_is_not_mistaken_for_an_envelope() {
+        assert!(!Envelope::is_envelope(b"gibbername-v1"));
+    }
+}
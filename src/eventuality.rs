@@ -0,0 +1,239 @@
+//! Confirmation tracking for registrations and transfers.
+//!
+//! An [`Eventuality`] pins down every field the resolving output must have -- denom, covhash,
+//! value, and binding data, plus (for transfers) which coin it must consume -- so [`wait_resolved`]
+//! only matches a transaction of exactly that shape. A transfer must also spend the name's own
+//! coin; a registration has no coin to pin, so two registrations with the same owner and binding
+//! bytes are not told apart (see [`Eventuality::registration`]).
+
+use melstructs::{Address, BlockHeight, CoinValue, Denom, Transaction, TxHash};
+
+/// How many blocks to scan forward before giving up on a registration or transfer confirming.
+pub const DEFAULT_TIMEOUT_BLOCKS: u64 = 100;
+
+/// The exact on-chain shape of the output we're waiting to see confirmed.
+#[derive(Debug, Clone)]
+pub struct Eventuality {
+    /// The denom of the gibbercoin we expect to see spent as an input, if any. `None` for a
+    /// brand-new registration, which creates the coin rather than spending an existing one.
+    consumed_denom: Option<Denom>,
+    /// The denom the resolving output must carry.
+    expected_denom: Denom,
+    /// The covenant hash (address) the resolving output must pay to.
+    expected_covhash: Address,
+    /// The value the resolving output must carry -- always 1 gibbercoin.
+    expected_value: CoinValue,
+    /// The binding bytes the resolving output's `additional_data` must carry.
+    expected_data: Vec<u8>,
+}
+
+impl Eventuality {
+    /// The eventuality for a brand-new name registration.
+    ///
+    /// Unlike [`Eventuality::transfer`], this has no prior coin to pin as `consumed_denom` --
+    /// `Denom::NewCustom` isn't unique to one registration -- so two registrations racing with the
+    /// same owner and the same `initial_binding` bytes can still resolve to whichever one the
+    /// watcher happens to see first. Callers that can't guarantee `initial_binding` is unique on
+    /// its own should fold in something that is (e.g. a random nonce); see `register()`'s use of
+    /// this.
+    pub fn registration(owner: Address, initial_binding: &[u8]) -> Self {
+        Self {
+            consumed_denom: None,
+            expected_denom: Denom::NewCustom,
+            expected_covhash: owner,
+            expected_value: CoinValue(1),
+            expected_data: initial_binding.to_vec(),
+        }
+    }
+
+    /// The eventuality for rebinding an existing gibbername, whose permanent identity is
+    /// `Denom::Custom(start_txhash)` regardless of how many times it has already transferred.
+    pub fn transfer(start_txhash: TxHash, owner: Address, new_binding: &[u8]) -> Self {
+        let denom = Denom::Custom(start_txhash);
+        Self {
+            consumed_denom: Some(denom),
+            expected_denom: denom,
+            expected_covhash: owner,
+            expected_value: CoinValue(1),
+            expected_data: new_binding.to_vec(),
+        }
+    }
+
+    /// If `tx` has an output matching this eventuality's expected shape, returns its position --
+    /// regardless of whether it also consumes `consumed_denom`, which the caller must still check
+    /// via [`Eventuality::resolved_by`] before treating the match as confirmed.
+    fn matching_output(&self, tx: &Transaction) -> Option<u32> {
+        tx.outputs
+            .iter()
+            .position(|out| {
+                out.denom == self.expected_denom
+                    && out.covhash == self.expected_covhash
+                    && out.value == self.expected_value
+                    && out.additional_data[..] == self.expected_data[..]
+            })
+            .map(|posn| posn as u32)
+    }
+
+    /// If `tx` resolves this eventuality, returns the position of the resolving output.
+    async fn resolved_by(
+        &self,
+        snapshot: &melprot::Snapshot,
+        tx: &Transaction,
+    ) -> anyhow::Result<Option<u32>> {
+        let posn = match self.matching_output(tx) {
+            Some(posn) => posn,
+            None => return Ok(None),
+        };
+
+        if let Some(consumed_denom) = self.consumed_denom {
+            let mut consumes_tracked_coin = false;
+            for input in &tx.inputs {
+                if let Some(source_tx) = snapshot.get_transaction(input.txhash).await? {
+                    if let Some(source_output) = source_tx.outputs.get(input.index as usize) {
+                        if source_output.denom == consumed_denom {
+                            consumes_tracked_coin = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            if !consumes_tracked_coin {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(posn))
+    }
+}
+
+/// Watches the chain starting at `start_height` for a transaction that resolves `eventuality`,
+/// returning the `(height, position within the block)` of the resolving transaction -- the pair a
+/// gibbername encodes.
+///
+/// Every candidate is re-checked against a freshly-fetched snapshot before being accepted, so a
+/// transaction that was briefly visible but then reorged out is never mistaken for confirmed.
+/// Gives up with an `Err` once `timeout_blocks` have passed with no resolution, rather than
+/// blocking forever.
+pub async fn wait_resolved(
+    client: &melprot::Client,
+    start_height: BlockHeight,
+    eventuality: &Eventuality,
+    timeout_blocks: u64,
+) -> anyhow::Result<(BlockHeight, u32)> {
+    let deadline = BlockHeight(start_height.0 + timeout_blocks);
+    let mut height = start_height;
+
+    loop {
+        let latest = client.latest_snapshot().await?.current_header().height;
+        if height > latest {
+            if height > deadline {
+                anyhow::bail!(
+                    "timed out after {timeout_blocks} blocks waiting for the transaction to confirm"
+                );
+            }
+            smol::Timer::after(std::time::Duration::from_secs(5)).await;
+            continue;
+        }
+
+        let snapshot = client.snapshot(height).await?;
+        let block = snapshot.current_block().await?;
+        for (posn, txhash) in block.abbreviate().txhashes.iter().copied().enumerate() {
+            let tx = match snapshot.get_transaction(txhash).await? {
+                Some(tx) => tx,
+                None => continue,
+            };
+            if eventuality.resolved_by(&snapshot, &tx).await?.is_some() {
+                // Re-validate against a fresh snapshot: a transaction we just saw may since have
+                // been reorged out of the canonical chain.
+                let fresh = client.snapshot(height).await?;
+                if fresh.get_transaction(txhash).await?.is_none() {
+                    continue;
+                }
+                return Ok((height, posn as u32));
+            }
+        }
+
+        if height >= deadline {
+            anyhow::bail!(
+                "timed out after {timeout_blocks} blocks waiting for the transaction to confirm"
+            );
+        }
+        height = BlockHeight(height.0 + 1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use melstructs::{CoinData, TxKind};
+    use std::str::FromStr;
+
+    fn owner() -> Address {
+        Address::from_str("t2k917e3f3r6wk5474sg3exmfpkh04a42w1chmek68fv5pnygywvsg").unwrap()
+    }
+
+    fn tx_with_single_output(coin: CoinData) -> Transaction {
+        Transaction {
+            kind: TxKind::Normal,
+            inputs: vec![],
+            outputs: vec![coin],
+            fee: CoinValue(0),
+            covenants: vec![],
+            data: vec![],
+            sigs: vec![],
+        }
+    }
+
+    #[test]
+    fn registration_matches_its_own_output() {
+        let eventuality = Eventuality::registration(owner(), b"henlo world lmao");
+        let tx = tx_with_single_output(CoinData {
+            covhash: owner(),
+            value: CoinValue(1),
+            denom: Denom::NewCustom,
+            additional_data: b"henlo world lmao".to_vec().into(),
+        });
+
+        assert_eq!(eventuality.matching_output(&tx), Some(0));
+    }
+
+    #[test]
+    fn registration_ignores_a_different_binding() {
+        let eventuality = Eventuality::registration(owner(), b"henlo world lmao");
+        let tx = tx_with_single_output(CoinData {
+            covhash: owner(),
+            value: CoinValue(1),
+            denom: Denom::NewCustom,
+            additional_data: b"some other binding".to_vec().into(),
+        });
+
+        assert_eq!(eventuality.matching_output(&tx), None);
+    }
+
+    #[test]
+    fn registration_ignores_a_different_value() {
+        let eventuality = Eventuality::registration(owner(), b"henlo world lmao");
+        let tx = tx_with_single_output(CoinData {
+            covhash: owner(),
+            value: CoinValue(2),
+            denom: Denom::NewCustom,
+            additional_data: b"henlo world lmao".to_vec().into(),
+        });
+
+        assert_eq!(eventuality.matching_output(&tx), None);
+    }
+
+    #[test]
+    fn transfer_matches_its_own_output_at_its_position() {
+        let start_txhash = TxHash::default();
+        let eventuality = Eventuality::transfer(start_txhash, owner(), b"new binding");
+        let tx = tx_with_single_output(CoinData {
+            covhash: owner(),
+            value: CoinValue(1),
+            denom: Denom::Custom(start_txhash),
+            additional_data: b"new binding".to_vec().into(),
+        });
+
+        assert_eq!(eventuality.matching_output(&tx), Some(0));
+    }
+}
@@ -1,7 +1,46 @@
 use anyhow::Context;
-use futures_util::StreamExt;
 use melstructs::{Address, BlockHeight, CoinData, CoinValue, Denom, Transaction, TxHash};
 
+mod checkpoint;
+mod covenant;
+mod crypto;
+mod eventuality;
+mod record;
+mod tx;
+pub use checkpoint::{Checkpoint, CheckpointCache, FileCheckpointCache, MemoryCheckpointCache};
+pub use covenant::Controller;
+pub use crypto::Envelope;
+pub use eventuality::{Eventuality, DEFAULT_TIMEOUT_BLOCKS};
+pub use record::{Field, FieldType, Record};
+pub use tx::{
+    build_register_transaction, build_register_transaction_encrypted, build_transfer_transaction,
+    build_transfer_transaction_encrypted, register_name_uri, transfer_name_uri,
+};
+
+/// Decodes a coin's `additional_data` into a [`Record`], transparently decrypting it with
+/// `viewing_secret` if it's an encrypted [`Envelope`] and a key was given. If it's encrypted and
+/// no key (or the wrong key) is given, returns [`Record::encrypted_placeholder`] rather than
+/// failing -- the chain stays auditable even when its content isn't.
+fn decode_binding(
+    version: record::Version,
+    additional_data: &[u8],
+    viewing_secret: Option<&x25519_dalek::StaticSecret>,
+) -> anyhow::Result<Record> {
+    if !crypto::Envelope::is_envelope(additional_data) {
+        return version.decode(additional_data);
+    }
+    match viewing_secret {
+        Some(viewing_secret) => {
+            let envelope = crypto::Envelope::decode(additional_data)?;
+            match crypto::decrypt(viewing_secret, &envelope) {
+                Ok(plaintext) => version.decode(&plaintext),
+                Err(_) => Ok(Record::encrypted_placeholder()),
+            }
+        }
+        None => Ok(Record::encrypted_placeholder()),
+    }
+}
+
 /// Decodes a gibbername into a blockchain location.
 fn decode_gibbername(gname: &str) -> anyhow::Result<(BlockHeight, u32)> {
     let (height, index) = gibbercode::decode(gname);
@@ -18,13 +57,13 @@ fn encode_gibbername(height: BlockHeight, index: u32) -> anyhow::Result<String>
 
 /// Gets and validates the starting transaction of the gibbername chain.
 /// Validation involves checking the transaction for the following properties:
-/// 1. The `data` field says "gibbername-v1"
+/// 1. The `data` field says "gibbername-v1" or "gibbername-v2"
 /// 2. The transaction has a single output with the [themelio_structs::Denom::NewCoin] denomination
 ///    with a value of 1
 async fn get_and_validate_start_tx(
     client: &melprot::Client,
     gibbername: &str,
-) -> anyhow::Result<(BlockHeight, TxHash)> {
+) -> anyhow::Result<(BlockHeight, TxHash, record::Version)> {
     let (height, index) = decode_gibbername(gibbername).expect("failed to decode {gibbername}");
     let snapshot = client.snapshot(height).await?;
     let txhash = snapshot.get_transaction_by_posn(index as usize).await?;
@@ -37,9 +76,7 @@ async fn get_and_validate_start_tx(
             .expect("expected transaction to exist, because txhash exists");
 
         // check the data
-        if &tx.data[..] != b"gibbername-v1" {
-            anyhow::bail!("invalid data in the start transaction: {:?}", tx.data);
-        }
+        let version = record::Version::from_tag(&tx.data)?;
 
         let new_outputs = tx
             .outputs
@@ -47,7 +84,7 @@ async fn get_and_validate_start_tx(
             .filter(|output| output.denom == Denom::NewCustom)
             .collect::<Vec<&CoinData>>();
         if new_outputs.len() == 1 && new_outputs[0].value == CoinValue(1) {
-            Ok((height, tx.hash_nosigs()))
+            Ok((height, tx.hash_nosigs(), version))
         } else {
             anyhow::bail!("invalid start transaction outputs");
         }
@@ -56,25 +93,66 @@ async fn get_and_validate_start_tx(
     }
 }
 
-/// Traverses the Catena chain to get the coin containing the final binding.
+/// Traverses the Catena chain, returning every hop's coin data from the registration output
+/// onward (the registration output is always `history[0]`).
+///
+/// If `cache` holds a checkpoint for `gibbername`, traversal resumes from there instead of from
+/// `start_height`/`start_txhash`, appending only the new hops. The checkpointed transaction is
+/// re-checked against a fresh snapshot first; if it's no longer found (the chain reorged out from
+/// under it), the checkpoint is invalidated and traversal restarts from the name's registration.
 async fn traverse_catena_chain(
     client: &melprot::Client,
+    gibbername: &str,
     start_height: BlockHeight,
     start_txhash: TxHash,
-) -> anyhow::Result<CoinData> {
+    expected_controller: Option<&Controller>,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<Vec<CoinData>> {
+    let denom = Denom::Custom(start_txhash);
+
+    let checkpoint = match cache.get(gibbername)? {
+        Some(checkpoint) => {
+            let still_reachable = match client.snapshot(checkpoint.resume_height).await {
+                Ok(snap) => matches!(snap.get_transaction(checkpoint.last_txhash).await, Ok(Some(_))),
+                Err(_) => false,
+            };
+            if still_reachable {
+                Some(checkpoint)
+            } else {
+                // the checkpointed transaction is no longer reachable -- most likely a reorg, but
+                // also covers a transient RPC failure fetching the snapshot or transaction. Either
+                // way, fall back to traversing from the name's registration rather than
+                // propagating the error.
+                cache.invalidate(gibbername)?;
+                None
+            }
+        }
+        None => None,
+    };
+
+    let (resume_height, resume_txhash, mut history) = match checkpoint {
+        Some(checkpoint) => (checkpoint.resume_height, checkpoint.last_txhash, checkpoint.history),
+        None => (start_height, start_txhash, vec![]),
+    };
+
     let traversal = client
-        .traverse_fwd(start_height, start_txhash, move |tx: &Transaction| {
+        .traverse_fwd(resume_height, resume_txhash, move |tx: &Transaction| {
             log::debug!("traversing {:?}", tx);
             tx.outputs.iter().position(|coin_data| {
                 (tx.hash_nosigs() == start_txhash && coin_data.denom == Denom::NewCustom)
-                    || coin_data.denom == Denom::Custom(start_txhash)
+                    || coin_data.denom == denom
             })
         })
         .expect("failed to traverse forward")
         .collect::<Vec<Transaction>>()
         .await;
 
-    if traversal.is_empty() {
+    // `traverse_fwd` hands back transactions, not the heights they confirmed at -- walk forward
+    // from the last known height to find each hop's real height, so the checkpoint we save below
+    // can be resumed from exactly (not merely before) the last validated hop.
+    let mut cursor_height = resume_height;
+
+    if history.is_empty() {
         let snap = client.snapshot(start_height).await?;
         let tx = snap
             .get_transaction(start_txhash)
@@ -83,124 +161,223 @@ async fn traverse_catena_chain(
         let coin = tx
             .outputs
             .iter()
-            .find(|coin| coin.denom == Denom::NewCustom);
+            .find(|coin| coin.denom == Denom::NewCustom)
+            .context("No valid gibbercoins found")?;
+        history.push(coin.clone());
+    }
 
-        match coin {
-            Some(coin_data) => return Ok(coin_data.clone()),
-            None => anyhow::bail!("No valid gibbercoins found"),
-        }
+    let mut last_txhash = resume_txhash;
+    for tx in &traversal {
+        let coin = tx
+            .outputs
+            .iter()
+            .find(|coin_data| coin_data.denom == denom)
+            .context("the name was permanently deleted")?;
+        history.push(coin.clone());
+        last_txhash = tx.hash_nosigs();
+        cursor_height = height_of_transaction(client, cursor_height, last_txhash).await?;
     }
 
-    let last_tx = traversal.last().expect("the traversal is empty");
-    if let Some(last_tx_coin) = last_tx
-        .outputs
-        .iter()
-        .find(|coin_data| coin_data.denom == Denom::Custom(start_txhash))
-    {
-        Ok(last_tx_coin.clone())
-    } else {
-        anyhow::bail!("the name was permanently deleted");
+    // Validate every hop, including the registration itself and any hops restored from a prior,
+    // possibly-unvalidated cache entry -- not just the hops fetched by this call. Otherwise a
+    // name registered straight to an attacker's address, and never transferred since, would pass
+    // validation against any expected_controller; and a single earlier `lookup()` with
+    // `expected_controller: None` would populate the checkpoint so every later
+    // `lookup_with_controller` call trusts it without re-checking the hops it didn't fetch itself.
+    if let Some(expected) = expected_controller {
+        expected.validate()?;
+        for coin in &history {
+            if coin.covhash != expected.covhash() {
+                anyhow::bail!(
+                    "gibbername {gibbername} hop's covhash does not match the expected controller"
+                );
+            }
+        }
     }
+
+    cache.put(
+        gibbername,
+        Checkpoint {
+            resume_height: cursor_height,
+            last_txhash,
+            denom,
+            history: history.clone(),
+        },
+    )?;
+
+    Ok(history)
 }
 
-/// Traverses the Catena chain to get the coin containing all the historical bindings.
-async fn traverse_catena_chain_whole_history(
+/// Scans forward block by block from `from_height` until `txhash` is found confirmed, returning
+/// that height. Used to pin a checkpoint to the exact height of its last validated hop, since
+/// `traverse_fwd` only hands back transactions, not the heights they confirmed at.
+async fn height_of_transaction(
     client: &melprot::Client,
-    start_height: BlockHeight,
-    start_txhash: TxHash,
-) -> anyhow::Result<Vec<CoinData>> {
-    let traversal = client
-        .traverse_fwd(start_height, start_txhash, move |tx: &Transaction| {
-            log::debug!("traversing {:?}", tx);
-            tx.outputs.iter().position(|coin_data| {
-                (tx.hash_nosigs() == start_txhash && coin_data.denom == Denom::NewCustom)
-                    || coin_data.denom == Denom::Custom(start_txhash)
-            })
-        })
-        .expect("failed to traverse forward")
-        .collect::<Vec<Transaction>>()
-        .await;
-
-    println!("{:?}", traversal);
-
-    let mut ret = vec![];
+    from_height: BlockHeight,
+    txhash: TxHash,
+) -> anyhow::Result<BlockHeight> {
+    let latest = client.latest_snapshot().await?.current_header().height;
+    let mut height = from_height;
+    loop {
+        if height > latest {
+            anyhow::bail!("could not find confirming block for transaction {txhash}");
+        }
+        let snapshot = client.snapshot(height).await?;
+        let block = snapshot.current_block().await?;
+        if block
+            .abbreviate()
+            .txhashes
+            .iter()
+            .any(|candidate| *candidate == txhash)
+        {
+            return Ok(height);
+        }
+        height = BlockHeight(height.0 + 1);
+    }
+}
 
-    let snap = client.snapshot(start_height).await?;
-    let tx = snap
-        .get_transaction(start_txhash)
-        .await?
-        .context("No transaction with given hash")?;
-    let coin = tx
-        .outputs
-        .iter()
-        .find(|coin| coin.denom == Denom::NewCustom);
+/// Placeholder text returned by [`lookup`] for a binding that's encrypted and can't be read
+/// without the viewing key -- see [`lookup_decrypted`].
+pub const ENCRYPTED_PLACEHOLDER: &str = "<encrypted>";
 
-    match coin {
-        Some(coin_data) => ret.push(coin_data.clone()),
-        None => anyhow::bail!("No valid gibbercoins found"),
+/// Returns the default field's text bound to the given gibbername if there is any.
+///
+/// If the binding is encrypted, this succeeds with [`ENCRYPTED_PLACEHOLDER`] rather than failing
+/// -- the gibbername and its chain are still structurally valid even when unreadable. Use
+/// [`lookup_decrypted`] to see the actual content.
+pub async fn lookup(
+    client: &melprot::Client,
+    gibbername: &str,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<String> {
+    let record = lookup_record(client, gibbername, cache).await?;
+    if record.is_encrypted() {
+        return Ok(ENCRYPTED_PLACEHOLDER.to_string());
     }
+    record
+        .default_text()
+        .context("binding has no default field")
+}
 
-    let lala: anyhow::Result<Vec<CoinData>> = traversal
-        .iter()
-        .map(|tx| {
-            if let Some(coin_data) = tx.outputs.iter().find(|coin_data| {
-                coin_data.denom == Denom::Custom(start_txhash)
-                    || coin_data.denom == Denom::NewCustom
-            }) {
-                Ok(coin_data.clone())
-            } else {
-                anyhow::bail!("OH NO! catena chain BROKE in the middle!")
-            }
-        })
-        .collect();
-    ret.extend(lala?);
+/// Like [`lookup`], but decrypts an encrypted binding using `viewing_secret`.
+pub async fn lookup_decrypted(
+    client: &melprot::Client,
+    gibbername: &str,
+    viewing_secret: &x25519_dalek::StaticSecret,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<Record> {
+    let (start_height, start_txhash, version) = get_and_validate_start_tx(client, gibbername).await?;
+    log::debug!("start_height: {start_height}, start_txhash: {start_txhash}");
+    let history =
+        traverse_catena_chain(client, gibbername, start_height, start_txhash, None, cache).await?;
+    let last_coin = history.last().context("no gibbercoins found")?;
+    decode_binding(version, &last_coin.additional_data, Some(viewing_secret))
+}
 
-    Ok(ret)
+/// Returns the fully parsed record currently bound to the given gibbername.
+pub async fn lookup_record(
+    client: &melprot::Client,
+    gibbername: &str,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<Record> {
+    let (record, _controller) = lookup_with_controller(client, gibbername, None, cache).await?;
+    Ok(record)
 }
 
-/// Returns the data bound to the given gibbername if there is any.
-pub async fn lookup(client: &melprot::Client, gibbername: &str) -> anyhow::Result<String> {
-    let (start_height, start_txhash) = get_and_validate_start_tx(client, gibbername).await?;
+/// Returns the record currently bound to the given gibbername, together with the covhash of the
+/// coin's controlling covenant (a single signer, or a compiled multisig script).
+///
+/// If `expected_controller` is given, every hop's output covhash is checked against it, so an
+/// organization co-owning a name via a multisig can verify the whole chain was only ever
+/// transferred by the threshold of signers it expects -- not just trust the latest hop.
+pub async fn lookup_with_controller(
+    client: &melprot::Client,
+    gibbername: &str,
+    expected_controller: Option<&Controller>,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<(Record, Address)> {
+    let (start_height, start_txhash, version) = get_and_validate_start_tx(client, gibbername).await?;
     log::debug!("start_height: {start_height}, start_txhash: {start_txhash}");
-    let last_coin = traverse_catena_chain(client, start_height, start_txhash).await?;
-    let binding = String::from_utf8_lossy(&last_coin.additional_data);
+    let history = traverse_catena_chain(
+        client,
+        gibbername,
+        start_height,
+        start_txhash,
+        expected_controller,
+        cache,
+    )
+    .await?;
+    let last_coin = history.last().context("no gibbercoins found")?;
+    let record = decode_binding(version, &last_coin.additional_data, None)?;
+    Ok((record, last_coin.covhash))
+}
 
-    Ok(binding.into_owned())
+/// Returns a single named field from the record currently bound to the given gibbername.
+pub async fn lookup_field(
+    client: &melprot::Client,
+    gibbername: &str,
+    field_name: &str,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<Vec<u8>> {
+    let record = lookup_record(client, gibbername, cache).await?;
+    record
+        .get(field_name)
+        .map(|field| field.value.clone())
+        .with_context(|| format!("no field named {field_name:?} in this gibbername's binding"))
 }
 
 /// Returns all the data ever bound to the given gibbername, if there is any
 pub async fn lookup_whole_history(
     client: &melprot::Client,
     gibbername: &str,
+    cache: &dyn CheckpointCache,
 ) -> anyhow::Result<Vec<String>> {
-    let (start_height, start_txhash) = get_and_validate_start_tx(client, gibbername).await?;
+    let (start_height, start_txhash, version) = get_and_validate_start_tx(client, gibbername).await?;
     log::debug!("start_height: {start_height}, start_txhash: {start_txhash}");
-    let all_coins = traverse_catena_chain_whole_history(client, start_height, start_txhash).await?;
+    let all_coins =
+        traverse_catena_chain(client, gibbername, start_height, start_txhash, None, cache).await?;
     let bindings: Vec<String> = all_coins
         .iter()
-        .map(|coin| String::from_utf8_lossy(&coin.additional_data).into_owned())
+        .map(|coin| {
+            let record = decode_binding(version, &coin.additional_data, None);
+            match record {
+                Ok(record) if record.is_encrypted() => ENCRYPTED_PLACEHOLDER.to_string(),
+                Ok(record) => record
+                    .default_text()
+                    .unwrap_or_else(|| String::from_utf8_lossy(&coin.additional_data).into_owned()),
+                Err(_) => String::from_utf8_lossy(&coin.additional_data).into_owned(),
+            }
+        })
         .collect();
     Ok(bindings)
 }
 
-#[allow(unused)]
-fn register_name_uri(address: Address, initial_binding: &str) -> String {
-    // melwallet_uri::MwUriBuilder::new()
-    //     .output(0, CoinData {
-    //         denom: NewCoin::Denom,
-    //         value: 1.into(),
-    //         covhash: address,
-    //         additional_data: initial_binding.as_bytes().into(),
-    //     })
-    //     .data(b"gibbername-v1")
-    //     .build()
-    todo!()
+/// Like [`lookup_whole_history`], but decrypts every hop it can using `viewing_secret`. Hops that
+/// were encrypted under a different key come back as [`Record::encrypted_placeholder`] rather
+/// than failing the whole lookup.
+pub async fn lookup_whole_history_decrypted(
+    client: &melprot::Client,
+    gibbername: &str,
+    viewing_secret: &x25519_dalek::StaticSecret,
+    cache: &dyn CheckpointCache,
+) -> anyhow::Result<Vec<Record>> {
+    let (start_height, start_txhash, version) = get_and_validate_start_tx(client, gibbername).await?;
+    log::debug!("start_height: {start_height}, start_txhash: {start_txhash}");
+    let all_coins =
+        traverse_catena_chain(client, gibbername, start_height, start_txhash, None, cache).await?;
+    Ok(all_coins
+        .iter()
+        .map(|coin| {
+            decode_binding(version, &coin.additional_data, Some(viewing_secret))
+                .unwrap_or_else(|_| Record::encrypted_placeholder())
+        })
+        .collect())
 }
 
 fn register_name_cmd(
     wallet_name: &str,
     address: Address,
-    initial_binding: &str,
+    additional_data: &[u8],
 ) -> anyhow::Result<String> {
     let cmd = format!(
         "melwallet-cli send -w {} --to {},{},{},\"{}\" --hex-data {}",
@@ -208,7 +385,7 @@ fn register_name_cmd(
         address,
         0.000001,
         "\"(NEWCUSTOM)\"",
-        hex::encode(initial_binding),
+        hex::encode(additional_data),
         hex::encode("gibbername-v1")
     );
 
@@ -222,31 +399,24 @@ pub async fn register(
     wallet_name: &str,
 ) -> anyhow::Result<String> {
     let height = client.latest_snapshot().await?.current_header().height;
-    let cmd = register_name_cmd(wallet_name, address, initial_binding)?;
+
+    // Fold a random dedup nonce into the broadcast additional_data (hidden from the displayed
+    // text by Record::decode_v1's NUL-terminated convention), so two concurrent registrations
+    // with the same owner and initial binding text still resolve to the right one -- the
+    // Eventuality below has no consumed coin to pin down, since Denom::NewCustom isn't unique.
+    let mut additional_data = initial_binding.as_bytes().to_vec();
+    additional_data.push(0);
+    let mut nonce = [0u8; 16];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut nonce);
+    additional_data.extend_from_slice(&nonce);
+
+    let cmd = register_name_cmd(wallet_name, address, &additional_data)?;
     println!("Send this command with your wallet: {}", cmd);
 
-    // scan through all transactions involving this address, starting at the block height right before we asked the user to send the transacton
-    let mut stream = client.stream_transactions_from(height, address).boxed();
-    while let Some((transaction, height)) = stream.next().await {
-        if &transaction.data[..] == b"gibbername-v1" {
-            let txhash = transaction.hash_nosigs();
-            let (posn, _) = client
-                .snapshot(height)
-                .await?
-                .current_block()
-                .await?
-                .abbreviate()
-                .txhashes
-                .iter()
-                .enumerate()
-                .find(|(_, hash)| **hash == txhash)
-                .expect("No transaction with matching hash in this block.");
-
-            let gibbername = encode_gibbername(height, posn as u32)?;
-            return Ok(gibbername);
-        }
-    }
-    unreachable!()
+    let eventuality = Eventuality::registration(address, &additional_data);
+    let (height, posn) =
+        eventuality::wait_resolved(client, height, &eventuality, DEFAULT_TIMEOUT_BLOCKS).await?;
+    encode_gibbername(height, posn)
 }
 
 pub async fn transfer_name_cmd(
@@ -277,25 +447,19 @@ pub async fn transfer_name_cmd(
 
     println!("Send this command with your wallet: {}", cmd);
 
-    // scan through all transactions involving this address, starting at the block height right before we asked the user to send the transacton
-    let mut stream = client
-        .stream_transactions_from(current_height, address)
-        .boxed();
-    while let Some((transaction, _height)) = stream.next().await {
-        if let Some(coin) = &transaction
-            .outputs
-            .iter()
-            .find(|coin| String::from_utf8_lossy(&coin.additional_data) == new_binding)
-        {
-            println!("COIN_DATA: {:?}", coin);
-            println!(
-                "Gibbername {} transferred to {} with new binding {}",
-                gibbername, address, new_binding
-            );
-            return Ok(());
-        }
-    }
-    unreachable!()
+    let eventuality = Eventuality::transfer(txhash, address, new_binding.as_bytes());
+    eventuality::wait_resolved(
+        client,
+        current_height,
+        &eventuality,
+        DEFAULT_TIMEOUT_BLOCKS,
+    )
+    .await?;
+    println!(
+        "Gibbername {} transferred to {} with new binding {}",
+        gibbername, address, new_binding
+    );
+    Ok(())
 }
 
 #[cfg(test)]
@@ -314,13 +478,14 @@ mod test {
                     .unwrap();
             let initial_binding = "henlo world lmao";
             let wallet_name = "alice";
+            let cache = MemoryCheckpointCache::new();
 
             let gibbername = register(&client, address, initial_binding, wallet_name)
                 .await
                 .unwrap();
 
             println!("gibbername: {gibbername}");
-            let binding = lookup(&client, &gibbername).await.unwrap();
+            let binding = lookup(&client, &gibbername, &cache).await.unwrap();
             println!("INITIAL BINDING: {}", binding);
 
             let new_binding = "it is wednesday my dudes";
@@ -333,10 +498,12 @@ mod test {
                 .await
                 .unwrap();
 
-            let final_lookup = lookup(&client, &gibbername).await.unwrap();
+            let final_lookup = lookup(&client, &gibbername, &cache).await.unwrap();
             println!("FINAL LOOKUP: {}", final_lookup);
 
-            let whole_history = lookup_whole_history(&client, &gibbername).await.unwrap();
+            let whole_history = lookup_whole_history(&client, &gibbername, &cache)
+                .await
+                .unwrap();
             println!("WHOLE HISTORY: {:?}", whole_history);
         });
 
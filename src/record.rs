@@ -0,0 +1,259 @@
+//! Structured binding records.
+//!
+//! A `gibbername-v2` record is a small self-describing, forward-compatible map of named fields,
+//! each carrying its own content type, so a single name can bind e.g. an address, a profile blob,
+//! and a TXT-style key/value set at once. `gibbername-v1` is treated as a single opaque text
+//! field named [`V1_FIELD_NAME`].
+//!
+//! The wire format of a `gibbername-v2` record (as stored in a coin's `additional_data`):
+//! `[ u16 LE field count ][ field ]*`, where each `field` is
+//! `[ u8 name length ][ name bytes ][ u8 content type ][ u32 LE value length ][ value bytes ]`.
+
+use std::convert::TryFrom;
+
+/// The name of the single field a `gibbername-v1` binding is treated as.
+pub const V1_FIELD_NAME: &str = "default";
+
+/// The content type of a record field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// UTF-8 text.
+    Text = 0,
+    /// Arbitrary bytes.
+    Bytes = 1,
+}
+
+impl FieldType {
+    fn from_tag(tag: u8) -> anyhow::Result<Self> {
+        match tag {
+            0 => Ok(FieldType::Text),
+            1 => Ok(FieldType::Bytes),
+            other => anyhow::bail!("unknown gibbername-v2 field content type: {other}"),
+        }
+    }
+}
+
+/// A single named field within a [`Record`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub content_type: FieldType,
+    pub value: Vec<u8>,
+}
+
+/// A parsed gibbername binding: one or more named fields.
+///
+/// A `gibbername-v1` binding parses as a single field named [`V1_FIELD_NAME`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Record {
+    pub fields: Vec<Field>,
+    /// Set when this record stands in for a binding we found encrypted but couldn't (or didn't
+    /// try to) decrypt -- the chain and its existence are still auditable, the content isn't.
+    pub encrypted: bool,
+}
+
+impl Record {
+    /// Builds a record with a single default text field, matching what `gibbername-v1` carried.
+    pub fn single(value: impl Into<String>) -> Self {
+        Self {
+            fields: vec![Field {
+                name: V1_FIELD_NAME.to_string(),
+                content_type: FieldType::Text,
+                value: value.into().into_bytes(),
+            }],
+            encrypted: false,
+        }
+    }
+
+    /// A placeholder standing in for a binding that's encrypted and not currently readable.
+    pub fn encrypted_placeholder() -> Self {
+        Self {
+            fields: vec![],
+            encrypted: true,
+        }
+    }
+
+    /// Whether this record is a stand-in for an encrypted binding we couldn't read.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// Looks up a field by name.
+    pub fn get(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the default field's value as a (lossily-decoded) string, if present.
+    pub fn default_text(&self) -> Option<String> {
+        self.get(V1_FIELD_NAME)
+            .map(|field| String::from_utf8_lossy(&field.value).into_owned())
+    }
+
+    /// Parses `additional_data` as a `gibbername-v1` binding: the displayed text is everything up
+    /// to the first NUL byte, if any. `register()` appends a NUL followed by a random dedup nonce
+    /// to disambiguate concurrent registrations sharing the same owner and binding text; that
+    /// suffix is never part of the displayed text.
+    pub fn decode_v1(additional_data: &[u8]) -> Self {
+        let visible = match additional_data.iter().position(|&b| b == 0) {
+            Some(nul_at) => &additional_data[..nul_at],
+            None => additional_data,
+        };
+        Self::single(String::from_utf8_lossy(visible).into_owned())
+    }
+
+    /// Parses `additional_data` as a `gibbername-v2` record.
+    pub fn decode_v2(additional_data: &[u8]) -> anyhow::Result<Self> {
+        let mut cursor = additional_data;
+        let field_count = take_u16(&mut cursor)?;
+
+        let mut fields = Vec::with_capacity(field_count as usize);
+        for _ in 0..field_count {
+            let name_len = take_u8(&mut cursor)? as usize;
+            let name = String::from_utf8(take_bytes(&mut cursor, name_len)?.to_vec())?;
+            let content_type = FieldType::from_tag(take_u8(&mut cursor)?)?;
+            let value_len = take_u32(&mut cursor)? as usize;
+            let value = take_bytes(&mut cursor, value_len)?.to_vec();
+            fields.push(Field {
+                name,
+                content_type,
+                value,
+            });
+        }
+
+        Ok(Self {
+            fields,
+            encrypted: false,
+        })
+    }
+
+    /// Encodes this record in the `gibbername-v2` wire format.
+    pub fn encode_v2(&self) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&u16::try_from(self.fields.len())?.to_le_bytes());
+        for field in &self.fields {
+            out.push(u8::try_from(field.name.len())?);
+            out.extend_from_slice(field.name.as_bytes());
+            out.push(field.content_type as u8);
+            out.extend_from_slice(&u32::try_from(field.value.len())?.to_le_bytes());
+            out.extend_from_slice(&field.value);
+        }
+        Ok(out)
+    }
+}
+
+fn take_bytes<'a>(cursor: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    if cursor.len() < len {
+        anyhow::bail!("truncated gibbername-v2 record");
+    }
+    let (taken, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(taken)
+}
+
+/// Which binding format a gibbername's start transaction declared, via its `data` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+impl Version {
+    /// Recognizes the start transaction's `data` tag.
+    pub fn from_tag(data: &[u8]) -> anyhow::Result<Self> {
+        match data {
+            b"gibbername-v1" => Ok(Version::V1),
+            b"gibbername-v2" => Ok(Version::V2),
+            other => anyhow::bail!("unrecognized gibbername record version tag: {:?}", other),
+        }
+    }
+
+    /// The wire tag for this version, as written to a start transaction's `data` field.
+    pub fn tag(self) -> &'static [u8] {
+        match self {
+            Version::V1 => b"gibbername-v1",
+            Version::V2 => b"gibbername-v2",
+        }
+    }
+
+    /// Decodes a coin's `additional_data` according to this version.
+    pub fn decode(self, additional_data: &[u8]) -> anyhow::Result<Record> {
+        match self {
+            Version::V1 => Ok(Record::decode_v1(additional_data)),
+            Version::V2 => Record::decode_v2(additional_data),
+        }
+    }
+}
+
+fn take_u8(cursor: &mut &[u8]) -> anyhow::Result<u8> {
+    Ok(take_bytes(cursor, 1)?[0])
+}
+
+fn take_u16(cursor: &mut &[u8]) -> anyhow::Result<u16> {
+    Ok(u16::from_le_bytes(take_bytes(cursor, 2)?.try_into()?))
+}
+
+fn take_u32(cursor: &mut &[u8]) -> anyhow::Result<u32> {
+    Ok(u32::from_le_bytes(take_bytes(cursor, 4)?.try_into()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn v1_decodes_as_single_default_field() {
+        let record = Record::decode_v1(b"henlo world lmao");
+        assert_eq!(record.default_text().unwrap(), "henlo world lmao");
+    }
+
+    #[test]
+    fn v1_strips_a_trailing_dedup_nonce() {
+        let mut additional_data = b"henlo world lmao".to_vec();
+        additional_data.push(0);
+        additional_data.extend_from_slice(&[1, 2, 3, 4]);
+
+        let record = Record::decode_v1(&additional_data);
+        assert_eq!(record.default_text().unwrap(), "henlo world lmao");
+    }
+
+    #[test]
+    fn v2_roundtrips_through_encode_and_decode() {
+        let record = Record {
+            fields: vec![
+                Field {
+                    name: V1_FIELD_NAME.to_string(),
+                    content_type: FieldType::Text,
+                    value: b"henlo world lmao".to_vec(),
+                },
+                Field {
+                    name: "avatar".to_string(),
+                    content_type: FieldType::Bytes,
+                    value: vec![0xde, 0xad, 0xbe, 0xef],
+                },
+            ],
+            encrypted: false,
+        };
+
+        let encoded = record.encode_v2().unwrap();
+        let decoded = Record::decode_v2(&encoded).unwrap();
+
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.default_text().unwrap(), "henlo world lmao");
+        assert_eq!(decoded.get("avatar").unwrap().value, vec![0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn v2_rejects_truncated_data() {
+        let record = Record::single("henlo");
+        let mut encoded = record.encode_v2().unwrap();
+        encoded.truncate(encoded.len() - 1);
+        assert!(Record::decode_v2(&encoded).is_err());
+    }
+
+    #[test]
+    fn version_tag_roundtrips() {
+        assert_eq!(Version::from_tag(b"gibbername-v1").unwrap(), Version::V1);
+        assert_eq!(Version::from_tag(b"gibbername-v2").unwrap(), Version::V2);
+        assert!(Version::from_tag(b"gibbername-v3").is_err());
+    }
+}
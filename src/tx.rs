@@ -0,0 +1,293 @@
+//! Transaction construction for gibbername registration and transfer.
+//!
+//! Builds unsigned [`Transaction`]s so any signer can fill in its own inputs and sign
+//! (PSBT-style: we build, something else signs), plus `melwallet://` deep links for wallets that
+//! can open a link and handle their own signing. Bindings are emitted as `gibbername-v2` records,
+//! and the destination is any [`Controller`] -- a single signer or an m-of-n multisig.
+
+use melstructs::{CoinData, CoinID, CoinValue, Denom, Transaction, TxHash, TxKind};
+
+use crate::covenant::Controller;
+use crate::record::{Record, Version};
+use crate::crypto;
+
+/// Builds the unsigned transaction that registers a brand-new gibbername.
+///
+/// The caller is responsible for selecting `inputs` that cover `fee` (the gibbercoin output is a
+/// newly minted `Denom::NewCustom` coin of value 1), and for signing the result with whatever key
+/// source controls those inputs.
+pub fn build_register_transaction(
+    inputs: Vec<CoinID>,
+    fee: CoinValue,
+    controller: &Controller,
+    initial_binding: &Record,
+) -> anyhow::Result<Transaction> {
+    controller.validate()?;
+    Ok(Transaction {
+        kind: TxKind::Normal,
+        inputs,
+        outputs: vec![CoinData {
+            covhash: controller.covhash(),
+            value: CoinValue(1),
+            denom: Denom::NewCustom,
+            additional_data: initial_binding.encode_v2()?.into(),
+        }],
+        fee,
+        covenants: vec![],
+        data: Version::V2.tag().to_vec(),
+        sigs: vec![],
+    })
+}
+
+/// Builds the unsigned transaction that rebinds an existing gibbername to `new_binding` and/or
+/// moves it to `controller`.
+///
+/// `start_txhash` is the hash of the gibbername's original registration transaction -- this is
+/// the permanent identity of the gibbercoin (`Denom::Custom(start_txhash)`) across every transfer.
+pub fn build_transfer_transaction(
+    inputs: Vec<CoinID>,
+    fee: CoinValue,
+    start_txhash: TxHash,
+    controller: &Controller,
+    new_binding: &Record,
+) -> anyhow::Result<Transaction> {
+    controller.validate()?;
+    Ok(Transaction {
+        kind: TxKind::Normal,
+        inputs,
+        outputs: vec![CoinData {
+            covhash: controller.covhash(),
+            value: CoinValue(1),
+            denom: Denom::Custom(start_txhash),
+            additional_data: new_binding.encode_v2()?.into(),
+        }],
+        fee,
+        covenants: vec![],
+        data: vec![],
+        sigs: vec![],
+    })
+}
+
+/// Builds the unsigned registration transaction with `initial_binding` sealed for
+/// `recipient_pubkey` instead of stored in the clear. The name's existence and chain remain
+/// publicly auditable; only the binding content is private.
+pub fn build_register_transaction_encrypted(
+    inputs: Vec<CoinID>,
+    fee: CoinValue,
+    controller: &Controller,
+    initial_binding: &Record,
+    recipient_pubkey: &x25519_dalek::PublicKey,
+) -> anyhow::Result<Transaction> {
+    controller.validate()?;
+    let envelope = crypto::encrypt(recipient_pubkey, &initial_binding.encode_v2()?);
+    Ok(Transaction {
+        kind: TxKind::Normal,
+        inputs,
+        outputs: vec![CoinData {
+            covhash: controller.covhash(),
+            value: CoinValue(1),
+            denom: Denom::NewCustom,
+            additional_data: envelope.encode().into(),
+        }],
+        fee,
+        covenants: vec![],
+        data: Version::V2.tag().to_vec(),
+        sigs: vec![],
+    })
+}
+
+/// Builds the unsigned transfer transaction with `new_binding` sealed for `recipient_pubkey`
+/// instead of stored in the clear.
+pub fn build_transfer_transaction_encrypted(
+    inputs: Vec<CoinID>,
+    fee: CoinValue,
+    start_txhash: TxHash,
+    controller: &Controller,
+    new_binding: &Record,
+    recipient_pubkey: &x25519_dalek::PublicKey,
+) -> anyhow::Result<Transaction> {
+    controller.validate()?;
+    let envelope = crypto::encrypt(recipient_pubkey, &new_binding.encode_v2()?);
+    Ok(Transaction {
+        kind: TxKind::Normal,
+        inputs,
+        outputs: vec![CoinData {
+            covhash: controller.covhash(),
+            value: CoinValue(1),
+            denom: Denom::Custom(start_txhash),
+            additional_data: envelope.encode().into(),
+        }],
+        fee,
+        covenants: vec![],
+        data: vec![],
+        sigs: vec![],
+    })
+}
+
+/// Builds a `melwallet://` deep link that registers a gibbername, for wallets that can open a
+/// link and handle their own input selection and signing.
+pub fn register_name_uri(controller: &Controller, initial_binding: &Record) -> anyhow::Result<String> {
+    controller.validate()?;
+    Ok(melwallet_uri::MwUriBuilder::new()
+        .output(
+            0,
+            CoinData {
+                covhash: controller.covhash(),
+                value: CoinValue(1),
+                denom: Denom::NewCustom,
+                additional_data: initial_binding.encode_v2()?.into(),
+            },
+        )
+        .data(Version::V2.tag())
+        .build())
+}
+
+/// Builds a `melwallet://` deep link that rebinds an existing gibbername, for wallets that can
+/// open a link and handle their own input selection and signing.
+pub fn transfer_name_uri(
+    start_txhash: TxHash,
+    controller: &Controller,
+    new_binding: &Record,
+) -> anyhow::Result<String> {
+    controller.validate()?;
+    Ok(melwallet_uri::MwUriBuilder::new()
+        .output(
+            0,
+            CoinData {
+                covhash: controller.covhash(),
+                value: CoinValue(1),
+                denom: Denom::Custom(start_txhash),
+                additional_data: new_binding.encode_v2()?.into(),
+            },
+        )
+        .build())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::OsRng;
+    use std::str::FromStr;
+    use x25519_dalek::{PublicKey, StaticSecret};
+
+    fn owner() -> Controller {
+        Controller::Single(
+            melstructs::Address::from_str(
+                "t2k917e3f3r6wk5474sg3exmfpkh04a42w1chmek68fv5pnygywvsg",
+            )
+            .unwrap(),
+        )
+    }
+
+    fn invalid_multisig() -> Controller {
+        Controller::Multisig {
+            threshold: 5,
+            signers: vec![owner().covhash()],
+            covhash: owner().covhash(),
+        }
+    }
+
+    #[test]
+    fn register_transaction_has_expected_output() {
+        let controller = owner();
+        let binding = Record::single("henlo world lmao");
+        let tx = build_register_transaction(vec![], CoinValue(10), &controller, &binding).unwrap();
+
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.outputs[0].denom, Denom::NewCustom);
+        assert_eq!(tx.outputs[0].covhash, controller.covhash());
+        assert_eq!(tx.outputs[0].value, CoinValue(1));
+        assert_eq!(tx.data, Version::V2.tag());
+        assert_eq!(
+            Record::decode_v2(&tx.outputs[0].additional_data).unwrap(),
+            binding
+        );
+    }
+
+    #[test]
+    fn transfer_transaction_has_expected_output() {
+        let controller = owner();
+        let start_txhash = TxHash::default();
+        let binding = Record::single("it is wednesday my dudes");
+        let tx =
+            build_transfer_transaction(vec![], CoinValue(10), start_txhash, &controller, &binding)
+                .unwrap();
+
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(tx.outputs[0].denom, Denom::Custom(start_txhash));
+        assert_eq!(tx.outputs[0].covhash, controller.covhash());
+        assert_eq!(
+            Record::decode_v2(&tx.outputs[0].additional_data).unwrap(),
+            binding
+        );
+    }
+
+    #[test]
+    fn encrypted_register_transaction_decrypts_to_the_same_binding() {
+        let recipient_secret = StaticSecret::random_from_rng(OsRng);
+        let recipient_pubkey = PublicKey::from(&recipient_secret);
+        let controller = owner();
+        let binding = Record::single("henlo world lmao");
+
+        let tx = build_register_transaction_encrypted(
+            vec![],
+            CoinValue(10),
+            &controller,
+            &binding,
+            &recipient_pubkey,
+        )
+        .unwrap();
+
+        let envelope = crypto::Envelope::decode(&tx.outputs[0].additional_data).unwrap();
+        let plaintext = crypto::decrypt(&recipient_secret, &envelope).unwrap();
+        assert_eq!(Record::decode_v2(&plaintext).unwrap(), binding);
+    }
+
+    #[test]
+    fn encrypted_transfer_transaction_decrypts_to_the_same_binding() {
+        let recipient_secret = StaticSecret::random_from_rng(OsRng);
+        let recipient_pubkey = PublicKey::from(&recipient_secret);
+        let controller = owner();
+        let start_txhash = TxHash::default();
+        let binding = Record::single("it's actually thursday my dudes");
+
+        let tx = build_transfer_transaction_encrypted(
+            vec![],
+            CoinValue(10),
+            start_txhash,
+            &controller,
+            &binding,
+            &recipient_pubkey,
+        )
+        .unwrap();
+
+        let envelope = crypto::Envelope::decode(&tx.outputs[0].additional_data).unwrap();
+        let plaintext = crypto::decrypt(&recipient_secret, &envelope).unwrap();
+        assert_eq!(Record::decode_v2(&plaintext).unwrap(), binding);
+    }
+
+    #[test]
+    fn builders_reject_an_unsatisfiable_multisig() {
+        let controller = invalid_multisig();
+        let binding = Record::single("henlo world lmao");
+
+        assert!(build_register_transaction(vec![], CoinValue(10), &controller, &binding).is_err());
+        assert!(build_transfer_transaction(
+            vec![],
+            CoinValue(10),
+            TxHash::default(),
+            &controller,
+            &binding
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn uri_builders_reject_an_unsatisfiable_multisig() {
+        let controller = invalid_multisig();
+        let binding = Record::single("henlo world lmao");
+
+        assert!(register_name_uri(&controller, &binding).is_err());
+        assert!(transfer_name_uri(TxHash::default(), &controller, &binding).is_err());
+    }
+}